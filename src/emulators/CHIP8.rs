@@ -3,11 +3,11 @@
 //! Written heavily with help from
 //! <https://multigesture.net/articles/how-to-write-an-emulator-chip-8-interpreter/>
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use rand::prelude::*;
 use wasm_bindgen::JsValue;
 use crate::wasm_bindgen;
-use super::Emulator;
+use super::{Emulator, Frame, KeyEvent};
 
 const FONTSET_SIZE: usize = 0x50;
 const MEMORY_SIZE: usize = 4096;
@@ -16,6 +16,235 @@ const GFX_H: usize = 32;
 const GFX_SIZE: usize = GFX_W * GFX_H;
 static CHIP8_FONTSET: [u8; FONTSET_SIZE] = [0; FONTSET_SIZE];
 
+/// The operand forms shared by most of the opcode table, decoded once up
+/// front so `e_execute_op` (and the disassembler) don't re-derive them inline.
+struct DecodedOp {
+    op:  u16,   // top nibble, still in place (opcode & 0xF000)
+    x:   usize, // (opcode & 0x0F00) >> 8
+    y:   usize, // (opcode & 0x00F0) >> 4
+    n:   u16,   // opcode & 0x000F
+    nnn: u16,   // opcode & 0x0FFF
+    kk:  u8,    // opcode & 0x00FF
+}
+
+fn decode(opcode: u16) -> DecodedOp {
+    DecodedOp {
+        op: opcode & 0xF000,
+        x: ((opcode & 0x0F00) >> 8) as usize,
+        y: ((opcode & 0x00F0) >> 4) as usize,
+        n: opcode & 0x000F,
+        nnn: opcode & 0x0FFF,
+        kk: (opcode & 0x00FF) as u8,
+    }
+}
+
+/// Decodes a single opcode into its canonical mnemonic text without executing it.
+fn mnemonic(opcode: u16) -> String {
+    let d = decode(opcode);
+    match d.op {
+        0x0000 => match opcode & 0xFF {
+            0xE0 => "CLS".to_string(),
+            0xEE => "RET".to_string(),
+            _ => format!("DW {:#06X}", opcode),
+        },
+        0x1000 => format!("JP {:#X}", d.nnn),
+        0x2000 => format!("CALL {:#X}", d.nnn),
+        0x3000 => format!("SE V{:X}, {:#X}", d.x, d.kk),
+        0x4000 => format!("SNE V{:X}, {:#X}", d.x, d.kk),
+        0x5000 => format!("SE V{:X}, V{:X}", d.x, d.y),
+        0x6000 => format!("LD V{:X}, {:#X}", d.x, d.kk),
+        0x7000 => format!("ADD V{:X}, {:#X}", d.x, d.kk),
+        0x8000 => match d.n {
+            0x0 => format!("LD V{:X}, V{:X}", d.x, d.y),
+            0x1 => format!("OR V{:X}, V{:X}", d.x, d.y),
+            0x2 => format!("AND V{:X}, V{:X}", d.x, d.y),
+            0x3 => format!("XOR V{:X}, V{:X}", d.x, d.y),
+            0x4 => format!("ADD V{:X}, V{:X}", d.x, d.y),
+            0x5 => format!("SUB V{:X}, V{:X}", d.x, d.y),
+            0x6 => format!("SHR V{:X}", d.x),
+            0x7 => format!("SUBN V{:X}, V{:X}", d.x, d.y),
+            0xE => format!("SHL V{:X}", d.x),
+            _ => format!("DW {:#06X}", opcode),
+        },
+        0x9000 => format!("SNE V{:X}, V{:X}", d.x, d.y),
+        0xA000 => format!("LD I, {:#X}", d.nnn),
+        0xB000 => format!("JP V0, {:#X}", d.nnn),
+        0xC000 => format!("RND V{:X}, {:#X}", d.x, d.kk),
+        0xD000 => format!("DRW V{:X}, V{:X}, {:#X}", d.x, d.y, d.n),
+        0xE000 => match opcode & 0xFF {
+            0x9E => format!("SKP V{:X}", d.x),
+            0xA1 => format!("SKNP V{:X}", d.x),
+            _ => format!("DW {:#06X}", opcode),
+        },
+        0xF000 => match opcode & 0xFF {
+            0x07 => format!("LD V{:X}, DT", d.x),
+            0x0A => format!("LD V{:X}, K", d.x),
+            0x15 => format!("LD DT, V{:X}", d.x),
+            0x18 => format!("LD ST, V{:X}", d.x),
+            0x1E => format!("ADD I, V{:X}", d.x),
+            0x29 => format!("LD F, V{:X}", d.x),
+            0x33 => format!("LD B, V{:X}", d.x),
+            0x55 => format!("LD [I], V{:X}", d.x),
+            0x65 => format!("LD V{:X}, [I]", d.x),
+            _ => format!("DW {:#06X}", opcode),
+        },
+        _ => format!("DW {:#06X}", opcode),
+    }
+}
+
+const AUDIO_BUFFER_CAPACITY: usize = 8192;
+const DEFAULT_SAMPLE_RATE: u32 = 44100;
+const DEFAULT_TONE_HZ: u32 = 440;
+const DEFAULT_AMPLITUDE: f32 = 0.25;
+
+/// Fixed-capacity circular buffer of PCM samples, written by the emulator's
+/// square-wave synthesizer and drained by the host's audio callback.
+struct SampleRingBuffer {
+    samples: [f32; AUDIO_BUFFER_CAPACITY],
+    head:    usize, // next write position
+    tail:    usize, // next read position
+    len:     usize, // number of buffered samples, for overrun/underrun detection
+}
+
+impl SampleRingBuffer {
+    fn new() -> Self {
+        Self { samples: [0.0; AUDIO_BUFFER_CAPACITY], head: 0, tail: 0, len: 0 }
+    }
+
+    /// Writes a sample, overwriting the oldest buffered sample on overrun.
+    fn push(&mut self, sample: f32) {
+        self.samples[self.head] = sample;
+        self.head = (self.head + 1) % AUDIO_BUFFER_CAPACITY;
+        if self.len == AUDIO_BUFFER_CAPACITY {
+            self.tail = (self.tail + 1) % AUDIO_BUFFER_CAPACITY;
+        } else {
+            self.len += 1;
+        }
+    }
+
+    /// Reads the oldest sample, or `None` on underrun.
+    fn pop(&mut self) -> Option<f32> {
+        if self.len == 0 {
+            return None;
+        }
+        let sample = self.samples[self.tail];
+        self.tail = (self.tail + 1) % AUDIO_BUFFER_CAPACITY;
+        self.len -= 1;
+        Some(sample)
+    }
+}
+
+/// Square-wave synthesizer state driven by the sound timer.
+struct AudioState {
+    buffer:      SampleRingBuffer,
+    sample_rate: u32,
+    tone_hz:     u32,
+    amplitude:   f32,
+    phase:       u32, // samples elapsed in the current square wave, carried across frames
+}
+
+impl AudioState {
+    fn new() -> Self {
+        Self {
+            buffer: SampleRingBuffer::new(),
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            tone_hz: DEFAULT_TONE_HZ,
+            amplitude: DEFAULT_AMPLITUDE,
+            phase: 0,
+        }
+    }
+}
+
+/// Configurable interpretations of opcodes whose behavior differs across
+/// CHIP-8 implementations. Defaults to classic COSMAC VIP semantics.
+struct Quirks {
+    shift_uses_vy:             bool, // 8XY6/8XYE shift Vy into Vx before shifting, instead of shifting Vx in place
+    load_store_increments_i:  bool, // FX55/FX65 advance I by x + 1 as a side effect
+    jump_with_offset_uses_vx: bool, // BXNN (SUPER-CHIP) uses Vx instead of BNNN's V0
+    clip_sprites:              bool, // DXYN clips sprites at the screen edge instead of wrapping
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_with_offset_uses_vx: false,
+            clip_sprites: true,
+        }
+    }
+}
+
+impl Quirks {
+    /// Named quirk profiles a frontend can select through `e_set_metadata`.
+    fn preset(name: &str) -> Option<Quirks> {
+        match name {
+            "vip" | "cosmac" => Some(Quirks::default()),
+            "schip" | "chip48" => Some(Quirks {
+                shift_uses_vy: false,
+                load_store_increments_i: false,
+                jump_with_offset_uses_vx: true,
+                clip_sprites: true,
+            }),
+            _ => None,
+        }
+    }
+}
+
+const SAVE_STATE_MAGIC: &[u8; 4] = b"AW8S";
+const SAVE_STATE_VERSION: u8 = 1;
+const SAVE_STATE_LEN: usize =
+    5 + 2 + MEMORY_SIZE + 16 + 2 + 2 + GFX_SIZE + 1 + 1 + (16 * 2) + 2 + 16 + 1 + 1;
+const REWIND_CAPACITY: usize = 600; // 10 seconds of history at 60 Hz
+
+fn read_u16(data: &[u8], pos: &mut usize) -> u16 {
+    let value = u16::from_le_bytes([data[*pos], data[*pos + 1]]);
+    *pos += 2;
+    value
+}
+
+/// Bounded history of serialized snapshots, one pushed per frame, for rewind.
+struct RewindBuffer {
+    snapshots: VecDeque<Vec<u8>>,
+}
+
+impl RewindBuffer {
+    fn new() -> Self {
+        Self { snapshots: VecDeque::with_capacity(REWIND_CAPACITY) }
+    }
+
+    fn push(&mut self, snapshot: Vec<u8>) {
+        if self.snapshots.len() == REWIND_CAPACITY {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+}
+
+/// The set of program-counter addresses a stepping debugger should halt execution at.
+#[derive(Default)]
+struct BreakpointSet {
+    addrs: HashSet<u16>,
+}
+
+impl BreakpointSet {
+    fn new() -> Self {
+        Self { addrs: HashSet::new() }
+    }
+
+    fn add(&mut self, pc: u16) {
+        self.addrs.insert(pc);
+    }
+
+    fn remove(&mut self, pc: u16) {
+        self.addrs.remove(&pc);
+    }
+
+    fn contains(&self, pc: u16) -> bool {
+        self.addrs.contains(&pc)
+    }
+}
+
 #[wasm_bindgen]
 pub struct CHIP8 {
     opcode:         u16,                // Opcode
@@ -35,10 +264,22 @@ pub struct CHIP8 {
 
     // Keyboard
     key:            [u8; 16],           // Key states
-    next_key:       i8,                 // Get next key (-2 is waiting, -1 is no key)
+    next_key:       i8,                 // Register waiting on FX0A, or -1 when not blocked (sentinel: next_key >= 0)
 
     // Draw flag
     draw_flag:      bool,               // Draw or not
+
+    // Debugger
+    breakpoints:    BreakpointSet,       // PC addresses that should halt run_until_break
+
+    // Audio
+    audio:          AudioState,          // Square-wave synthesizer fed by the sound timer
+
+    // Compatibility
+    quirks:         Quirks,              // Selects between ambiguous opcode interpretations
+
+    // Rewind
+    rewind_buffer:  RewindBuffer,        // Per-frame snapshots for rewind()
 }
 
 impl Emulator for CHIP8 {
@@ -57,6 +298,10 @@ impl Emulator for CHIP8 {
             key: [0; 16],
             next_key: -1,
             draw_flag: false,
+            breakpoints: BreakpointSet::new(),
+            audio: AudioState::new(),
+            quirks: Quirks::default(),
+            rewind_buffer: RewindBuffer::new(),
         };
 
         // Font starts at 0x50 = 80
@@ -77,11 +322,12 @@ impl Emulator for CHIP8 {
         // Decode opcode
         // https://en.wikipedia.org/wiki/CHIP-8#Opcode_table
 
-        let code_type = self.opcode & 0xF000;
-        match code_type {
-            0x0 => {
-                let code_type = self.opcode & 0xFF;
-                match code_type {
+        self.opcode = opcode as u16;
+        let d = decode(self.opcode);
+
+        match d.op {
+            0x0000 => {
+                match self.opcode & 0xFF {
                     0xE0 => {
                         self.gfx = [0; GFX_SIZE];
                         self.draw_flag = true;
@@ -91,129 +337,148 @@ impl Emulator for CHIP8 {
                             eprintln!("Bottom of stack, cannot return! at: {0}", self.pc);
                             return;
                         }
-                        self.pc = self.stack[self.sp - 1];
+                        self.pc = self.stack[(self.sp - 1) as usize];
                         self.sp -= 1;
                     },
                     _ => { eprintln!("Unknown opcode: {0}", self.opcode); }
                 }
             },
             0x1000 => {
-                self.pc = self.opcode & 0xFFF;
+                self.pc = d.nnn;
             },
             0x2000 => {
-                self.stack[self.sp] = self.pc;
+                self.stack[self.sp as usize] = self.pc;
                 self.sp += 1;
-                self.pc = self.opcode & 0xFFF;
+                self.pc = d.nnn;
             },
             0x3000 => {
-                let reg = (self.opcode & 0xF00) >> 16;
-                if self.V[reg] == self.opcode & 0xFF {
+                if self.V[d.x] == d.kk {
                     self.pc += 4;
                 } else {
                     self.pc += 2;
                 }
             },
             0x4000 => {
-                let reg = (self.opcode & 0xF00) >> 16;
-                if self.V[reg] != self.opcode & 0xFF {
+                if self.V[d.x] != d.kk {
                     self.pc += 4;
                 } else {
                     self.pc += 2;
                 }
             },
             0x5000 => {
-                let reg_x = (self.opcode & 0xF00) >> 16;
-                let reg_y = (self.opcode & 0xF0) >> 8;
-                if self.V[reg_x] == self.V[reg_y] {
+                if self.V[d.x] == self.V[d.y] {
                     self.pc += 4;
                 } else {
                     self.pc += 2;
                 }
             },
             0x6000 => {
-                let reg = (self.opcode & 0xF00) >> 16;
-                self.V[reg] = self.opcode & 0xFF;
+                self.V[d.x] = d.kk;
                 self.pc += 2;
             },
             0x7000 => {
-                let reg = (self.opcode & 0xF00) >> 16;
-                self.V[reg] += self.opcode & 0xFF;
+                self.V[d.x] = self.V[d.x].wrapping_add(d.kk);
                 self.pc += 2;
             },
             0x8000 => {
-                let code_type = self.opcode & 0xF;
-                let reg_x = (self.opcode & 0xF00) >> 16;
-                let reg_y = (self.opcode & 0xF0) >> 8;
-                match code_type {
-                    0x0 => { self.V[reg_x] = self.V[reg_y]; self.pc += 2; },
-                    0x1 => { self.V[reg_x] |= self.V[reg_y]; self.pc += 2; },
-                    0x2 => { self.V[reg_x] &= self.V[reg_y]; self.pc += 2; },
-                    0x3 => { self.V[reg_x] ^= self.V[reg_y]; self.pc += 2; },
-                    0x4 => { self.V[reg_x] += self.V[reg_y]; self.pc += 2; },
-                    0x5 => { self.V[reg_x] -= self.V[reg_y]; self.pc += 2; },
-                    0x6 => { self.V[0xF] = self.V[reg_x] & 0b1; self.V[reg_x] >>= 1; self.pc += 2; },
-                    0x7 => { self.V[reg_x] = self.V[reg_y] - self.V[reg_x]; self.pc += 2; },
-                    0xE => { self.V[0xF] = self.V[reg_x] & 0b10000000; self.V[reg_x] <<= 1; self.pc += 2; },
+                match d.n {
+                    0x0 => { self.V[d.x] = self.V[d.y]; self.pc += 2; },
+                    0x1 => { self.V[d.x] |= self.V[d.y]; self.pc += 2; },
+                    0x2 => { self.V[d.x] &= self.V[d.y]; self.pc += 2; },
+                    0x3 => { self.V[d.x] ^= self.V[d.y]; self.pc += 2; },
+                    0x4 => {
+                        let (result, carry) = self.V[d.x].overflowing_add(self.V[d.y]);
+                        self.V[d.x] = result;
+                        self.V[0xF] = carry as u8;
+                        self.pc += 2;
+                    },
+                    0x5 => {
+                        let (result, borrow) = self.V[d.x].overflowing_sub(self.V[d.y]);
+                        self.V[d.x] = result;
+                        self.V[0xF] = !borrow as u8;
+                        self.pc += 2;
+                    },
+                    0x6 => {
+                        let value = if self.quirks.shift_uses_vy { self.V[d.y] } else { self.V[d.x] };
+                        self.V[d.x] = value >> 1;
+                        self.V[0xF] = value & 0b1;
+                        self.pc += 2;
+                    },
+                    0x7 => {
+                        let (result, borrow) = self.V[d.y].overflowing_sub(self.V[d.x]);
+                        self.V[d.x] = result;
+                        self.V[0xF] = !borrow as u8;
+                        self.pc += 2;
+                    },
+                    0xE => {
+                        let value = if self.quirks.shift_uses_vy { self.V[d.y] } else { self.V[d.x] };
+                        self.V[d.x] = value << 1;
+                        self.V[0xF] = (value & 0b10000000) >> 7;
+                        self.pc += 2;
+                    },
                     _ => { eprintln!("Unknown opcode: {0}", self.opcode); },
                 }
             },
             0x9000 => {
-                let reg_x = (self.opcode & 0xF00) >> 16;
-                let reg_y = (self.opcode & 0xF0) >> 8;
-                if self.V[reg_x] != self.V[reg_y] {
+                if self.V[d.x] != self.V[d.y] {
                     self.pc += 4;
                 } else {
                     self.pc += 2;
                 }
             },
             0xA000 => { // Sets I to the address NNN.
-                self.i = self.opcode & 0xFFF;
+                self.I = d.nnn;
                 self.pc += 2;
             },
             0xB000 => {
-                self.pc = self.V[0] as u16 + (self.opcode & 0xFFF);
+                let base = if self.quirks.jump_with_offset_uses_vx { self.V[d.x] } else { self.V[0] };
+                self.pc = base as u16 + d.nnn;
             },
             0xC000 => {
-                let reg = (self.opcode & 0xF00) >> 16;
-                self.V[reg] = rand::random::<u8>() & self.opcode & 0xFF;
+                self.V[d.x] = rand::random::<u8>() & d.kk;
                 self.pc += 2;
             },
             0xD000 => {
-                let reg_x = (self.opcode & 0xF00) >> 16;
-                let reg_y = (self.opcode & 0xF0) >> 8;
-                let height = self.opcode & 0xF;
-                let start_position = self.V[reg_y] * GFX_W + self.V[reg_x];
+                let origin_x = self.V[d.x] as usize % GFX_W;
+                let origin_y = self.V[d.y] as usize % GFX_H;
+                let height = d.n as usize;
                 self.V[0xF] = 0;
-                for i in 0..height { // Paint row of 8 pixels at a time
-                    self.V[0xF] |= {
-                        let pos_gfx = start_position + (i * GFX_W);
-                        let pos_mem = self.I + i;
-                        let mut x = self.memory[pos_mem];
-                        let mut r = 0;
-                        for j in 0..8 {
-                            r |= (self.gfx[pos_gfx + 7 - j]) ^ (x % 2);
-                            self.gfx[pos_gfx + 7 - j] = x % 2;
-                            x >>= 1;
+                'rows: for row in 0..height { // Paint row of 8 pixels at a time
+                    let y = origin_y + row;
+                    let y = if y >= GFX_H {
+                        if self.quirks.clip_sprites { continue 'rows; }
+                        y % GFX_H
+                    } else { y };
+                    let sprite_byte = self.memory[self.I as usize + row];
+                    for col in 0..8 {
+                        let x = origin_x + col;
+                        let x = if x >= GFX_W {
+                            if self.quirks.clip_sprites { continue; }
+                            x % GFX_W
+                        } else { x };
+                        let sprite_pixel = (sprite_byte >> (7 - col)) & 1;
+                        let idx = y * GFX_W + x;
+                        if sprite_pixel == 1 && self.gfx[idx] == 1 {
+                            self.V[0xF] = 1;
                         }
-                        r
-                    };
+                        self.gfx[idx] ^= sprite_pixel;
+                    }
                 }
+                self.draw_flag = true;
                 self.pc += 2;
             },
             0xE000 => {
-                let code_type = self.opcode & 0xFF;
-                let reg = (self.opcode & 0xF00) >> 16;
-                let key = self.key[self.V[reg]];
-                match code_type {
+                let key = self.key[self.V[d.x] as usize];
+                match self.opcode & 0xFF {
                     0x9E => {
-                        if key {
+                        if key != 0 {
                             self.pc += 4;
                         } else {
                             self.pc += 2;
                         }
                     },
                     0xA1 => {
-                        if !key {
+                        if key == 0 {
                             self.pc += 4;
                         } else {
                             self.pc += 2;
@@ -223,29 +488,34 @@ impl Emulator for CHIP8 {
                 }
             },
             0xF000 => {
-                let code_type = self.opcode & 0xFF;
-                let reg = (self.opcode & 0xF00) >> 16;
-                match code_type {
+                let reg = d.x;
+                match self.opcode & 0xFF {
                     0x07 => { self.V[reg] = self.delay_timer; self.pc += 2; },
-                    0x0A => { self.next_key = -2; },
+                    0x0A => { self.next_key = reg as i8; },
                     0x15 => { self.delay_timer = self.V[reg]; self.pc += 2; },
                     0x18 => { self.sound_timer = self.V[reg]; self.pc += 2; },
-                    0x1E => { self.I += self.V[reg]; self.pc += 2; },
-                    0x29 => { self.I = self.V[reg]; self.pc += 2; },
+                    0x1E => { self.I += self.V[reg] as u16; self.pc += 2; },
+                    0x29 => { self.I = self.V[reg] as u16; self.pc += 2; },
                     0x33 => {
-                        self.memory[self.I] = self.V[reg] / 100;
-                        self.memory[self.I + 1] = (self.V[reg] / 10) % 10;
-                        self.memory[self.I + 2] = self.V[reg] % 10;
+                        self.memory[self.I as usize] = self.V[reg] / 100;
+                        self.memory[self.I as usize + 1] = (self.V[reg] / 10) % 10;
+                        self.memory[self.I as usize + 2] = self.V[reg] % 10;
                         self.pc += 2;
                     },
                     0x55 => {
-                        let mem = &mut self.memory[self.I..self.I + reg + 1];
-                        mem[..reg + 1].clone_from_slice(self.V[..reg + 1]);
+                        let i = self.I as usize;
+                        self.memory[i..=i + reg].clone_from_slice(&self.V[..=reg]);
+                        if self.quirks.load_store_increments_i {
+                            self.I += reg as u16 + 1;
+                        }
                         self.pc += 2;
                     },
                     0x65 => {
-                        let registers = &mut self.V[..];
-                        registers[..reg + 1].clone_from_slice(self.memory[self.I..self.I + reg + 1]);
+                        let i = self.I as usize;
+                        self.V[..=reg].clone_from_slice(&self.memory[i..=i + reg]);
+                        if self.quirks.load_store_increments_i {
+                            self.I += reg as u16 + 1;
+                        }
                         self.pc += 2;
                     },
                     _ => { eprintln!("Unknown opcode: {0}", self.opcode); }
@@ -256,44 +526,53 @@ impl Emulator for CHIP8 {
     }
 
     fn e_update(&mut self) {
-        if self.next_key == -2 {
-            todo!("Block and get next key");
-            self.pc += 2;
-        } else if self.next_key >= 0 {
-            self.key[self.next_key] = 1;
-        }
-
-        // Set opcode stored in big endian
-        self.opcode = self.memory[self.pc] << 8 | self.memory[self.pc + 1];
-
-        self.e_execute_op(self.opcode as u64);
+        // Snapshot before mutating so rewind(1) restores the state as it was
+        // prior to this frame, not the frame that was just produced.
+        let snapshot = self.save_state();
+        self.rewind_buffer.push(snapshot);
+        self.cycle();
+        self.tick_timers();
+    }
 
-        // Update timers
-        if self.delay_timer > 0 {
-            self.delay_timer -= 1;
+    fn e_set_metadata(&mut self, metadata: HashMap<String, JsValue>) {
+        if let Some(hz) = metadata.get("sample_rate").and_then(JsValue::as_f64) {
+            self.set_sample_rate(hz as u32);
         }
 
-        if self.sound_timer > 0 {
-            if self.sound_timer == 1 {
-                println!("BEEP!");
+        if let Some(profile) = metadata.get("quirks").and_then(JsValue::as_string) {
+            if let Some(quirks) = Quirks::preset(&profile) {
+                self.quirks = quirks;
+            } else {
+                eprintln!("Unknown quirks profile: {0}", profile);
             }
-            self.sound_timer -= 1;
         }
     }
 
-    fn e_set_metadata(&mut self, metadata: HashMap<String, JsValue>) { /* Nothing is required to set */ }
-
     fn e_draw(&mut self) {
         if !self.draw_flag {
             return;
         }
-        todo!();
 
         self.draw_flag = false;
     }
 
-    fn e_set_input(&mut self) {
-        todo!()
+    fn present_frame(&self) -> Frame {
+        Frame::new(GFX_W as u32, GFX_H as u32, self.gfx.to_vec())
+    }
+
+    fn e_set_input(&mut self, event: KeyEvent) {
+        let key = event.key as usize;
+        if key >= self.key.len() {
+            return;
+        }
+        self.key[key] = if event.pressed { 1 } else { 0 };
+
+        // Satisfies a blocked FX0A: store the pressed key in the waiting register and resume.
+        if event.pressed && self.next_key >= 0 {
+            self.V[self.next_key as usize] = event.key;
+            self.next_key = -1;
+            self.pc += 2;
+        }
     }
 
     fn e_reset(&mut self) {
@@ -307,6 +586,47 @@ impl Emulator for CHIP8 {
     }
 }
 
+impl CHIP8 {
+    /// Runs exactly one fetch-decode-execute cycle, without ticking the 60 Hz timers.
+    fn cycle(&mut self) {
+        // Blocked on FX0A until e_set_input delivers a key press.
+        if self.next_key >= 0 {
+            return;
+        }
+
+        // Set opcode stored in big endian
+        let pc = self.pc as usize;
+        self.opcode = (self.memory[pc] as u16) << 8 | self.memory[pc + 1] as u16;
+
+        self.e_execute_op(self.opcode as u64);
+    }
+
+    /// Decrements the delay and sound timers, at the emulator's 60 Hz rate.
+    fn tick_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+
+        if self.sound_timer > 0 {
+            self.push_audio_frame();
+            self.sound_timer -= 1;
+        }
+    }
+
+    /// Appends one frame's worth of square wave samples to the audio buffer,
+    /// carrying the oscillator phase across frames so there are no clicks.
+    fn push_audio_frame(&mut self) {
+        let samples_per_frame = self.audio.sample_rate / 60;
+        let half_period = (self.audio.sample_rate / (2 * self.audio.tone_hz)).max(1);
+        let amplitude = self.audio.amplitude;
+        for _ in 0..samples_per_frame {
+            let sample = if (self.audio.phase / half_period) % 2 == 0 { amplitude } else { -amplitude };
+            self.audio.buffer.push(sample);
+            self.audio.phase = self.audio.phase.wrapping_add(1);
+        }
+    }
+}
+
 #[wasm_bindgen]
 impl CHIP8 {
     /// Initializes a new CHIP8 emulator object
@@ -325,15 +645,178 @@ impl CHIP8 {
     #[wasm_bindgen]
     pub fn draw(&mut self) { self.e_draw() }
 
+    /// Returns the current display contents for a canvas renderer to present
+    #[wasm_bindgen]
+    pub fn present_frame(&self) -> Frame { Emulator::present_frame(self) }
+
     /// Function for setting arbitrary metadata for the system
     #[wasm_bindgen]
     pub fn set_metadata(&mut self, metadata: HashMap<String, JsValue>) { self.e_set_metadata(metadata) }
 
     /// Sets the hex keyboard input
     #[wasm_bindgen]
-    pub fn set_input(&mut self) { self.e_set_input() }
+    pub fn set_input(&mut self, event: KeyEvent) { self.e_set_input(event) }
 
     /// Resets the memory of the CHIP8 emulator
     #[wasm_bindgen]
     pub fn reset(&mut self) { self.e_reset() }
+
+    /// Disassembles `len` bytes of memory starting at `start` into a list of
+    /// mnemonic strings, one per instruction. Unknown opcodes decode to `DW 0xNNNN`.
+    #[wasm_bindgen]
+    pub fn disassemble(&self, start: u16, len: u16) -> Vec<String> {
+        let mut out = Vec::new();
+        let mut offset: u16 = 0;
+        while offset + 1 < len {
+            let addr = (start + offset) as usize;
+            let hi = *self.memory.get(addr).unwrap_or(&0) as u16;
+            let lo = *self.memory.get(addr + 1).unwrap_or(&0) as u16;
+            out.push(mnemonic((hi << 8) | lo));
+            offset += 2;
+        }
+        out
+    }
+
+    /// Executes exactly one fetch-decode-execute cycle, without ticking the timers
+    #[wasm_bindgen]
+    pub fn step(&mut self) { self.cycle() }
+
+    /// Adds a breakpoint at the given program counter address
+    #[wasm_bindgen]
+    pub fn add_breakpoint(&mut self, pc: u16) { self.breakpoints.add(pc) }
+
+    /// Removes a breakpoint at the given program counter address
+    #[wasm_bindgen]
+    pub fn remove_breakpoint(&mut self, pc: u16) { self.breakpoints.remove(pc) }
+
+    /// Steps until the program counter hits a breakpoint or `max_cycles` have run,
+    /// returning the number of cycles actually executed
+    #[wasm_bindgen]
+    pub fn run_until_break(&mut self, max_cycles: u32) -> u32 {
+        let mut cycles = 0;
+        while cycles < max_cycles {
+            if self.breakpoints.contains(self.pc) {
+                break;
+            }
+            self.cycle();
+            cycles += 1;
+        }
+        cycles
+    }
+
+    /// Dumps V0..VF, I, pc, and sp as a flat little-endian byte buffer
+    #[wasm_bindgen]
+    pub fn dump_registers(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(16 + 2 + 2 + 2);
+        out.extend_from_slice(&self.V);
+        out.extend_from_slice(&self.I.to_le_bytes());
+        out.extend_from_slice(&self.pc.to_le_bytes());
+        out.extend_from_slice(&self.sp.to_le_bytes());
+        out
+    }
+
+    /// Dumps the active call stack (the `sp` entries that are actually in use)
+    #[wasm_bindgen]
+    pub fn dump_stack(&self) -> Vec<u16> {
+        self.stack[..self.sp as usize].to_vec()
+    }
+
+    /// Reads `len` bytes of memory starting at `addr`, clamped to the end of memory
+    #[wasm_bindgen]
+    pub fn read_memory(&self, addr: u16, len: u16) -> Vec<u8> {
+        let start = addr as usize;
+        let end = (start + len as usize).min(MEMORY_SIZE);
+        if start >= end {
+            return Vec::new();
+        }
+        self.memory[start..end].to_vec()
+    }
+
+    /// Fills `out` with buffered audio samples, emitting silence for any underrun
+    #[wasm_bindgen]
+    pub fn fill_audio(&mut self, out: &mut [f32]) {
+        for slot in out.iter_mut() {
+            *slot = self.audio.buffer.pop().unwrap_or(0.0);
+        }
+    }
+
+    /// Sets the audio sample rate used when synthesizing the sound-timer square wave
+    #[wasm_bindgen]
+    pub fn set_sample_rate(&mut self, hz: u32) {
+        self.audio.sample_rate = hz;
+    }
+
+    /// Serializes the complete machine state into a versioned byte blob
+    #[wasm_bindgen]
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(SAVE_STATE_LEN);
+        out.extend_from_slice(SAVE_STATE_MAGIC);
+        out.push(SAVE_STATE_VERSION);
+        out.extend_from_slice(&self.opcode.to_le_bytes());
+        out.extend_from_slice(&self.memory);
+        out.extend_from_slice(&self.V);
+        out.extend_from_slice(&self.I.to_le_bytes());
+        out.extend_from_slice(&self.pc.to_le_bytes());
+        out.extend_from_slice(&self.gfx);
+        out.push(self.delay_timer);
+        out.push(self.sound_timer);
+        for slot in &self.stack {
+            out.extend_from_slice(&slot.to_le_bytes());
+        }
+        out.extend_from_slice(&self.sp.to_le_bytes());
+        out.extend_from_slice(&self.key);
+        out.push(self.next_key as u8);
+        out.push(self.draw_flag as u8);
+        out
+    }
+
+    /// Restores the complete machine state from a blob produced by `save_state`
+    #[wasm_bindgen]
+    pub fn load_state(&mut self, data: Vec<u8>) {
+        if data.len() != SAVE_STATE_LEN || &data[0..4] != SAVE_STATE_MAGIC {
+            eprintln!("Invalid save state: bad magic or length");
+            return;
+        }
+        if data[4] != SAVE_STATE_VERSION {
+            eprintln!("Invalid save state: unsupported version {0}", data[4]);
+            return;
+        }
+
+        let mut pos = 5;
+        self.opcode = read_u16(&data, &mut pos);
+        self.memory.copy_from_slice(&data[pos..pos + MEMORY_SIZE]); pos += MEMORY_SIZE;
+        self.V.copy_from_slice(&data[pos..pos + 16]); pos += 16;
+        self.I = read_u16(&data, &mut pos);
+        self.pc = read_u16(&data, &mut pos);
+        self.gfx.copy_from_slice(&data[pos..pos + GFX_SIZE]); pos += GFX_SIZE;
+        self.delay_timer = data[pos]; pos += 1;
+        self.sound_timer = data[pos]; pos += 1;
+        for slot in self.stack.iter_mut() {
+            *slot = read_u16(&data, &mut pos);
+        }
+        self.sp = read_u16(&data, &mut pos);
+        self.key.copy_from_slice(&data[pos..pos + 16]); pos += 16;
+        self.next_key = data[pos] as i8; pos += 1;
+        self.draw_flag = data[pos] != 0;
+    }
+
+    /// Rewinds the machine state back by `frames` frames, using the per-frame
+    /// snapshots captured in `update`. Clamped to however much history is available.
+    #[wasm_bindgen]
+    pub fn rewind(&mut self, frames: u32) {
+        let mut remaining = frames;
+        let mut target = None;
+        while remaining > 0 {
+            match self.rewind_buffer.snapshots.pop_back() {
+                Some(snapshot) => {
+                    target = Some(snapshot);
+                    remaining -= 1;
+                },
+                None => break,
+            }
+        }
+        if let Some(snapshot) = target {
+            self.load_state(snapshot);
+        }
+    }
 }
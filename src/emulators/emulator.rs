@@ -2,6 +2,52 @@ use std::collections::HashMap;
 use wasm_bindgen::JsValue;
 use crate::wasm_bindgen;
 
+/// A monochrome pixel buffer produced by an emulator's display, along with
+/// the dimensions needed to interpret it. Carrying its own dimensions lets a
+/// single renderer support emulators whose display isn't 64x32.
+#[wasm_bindgen]
+pub struct Frame {
+    width:  u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl Frame {
+    /// Width of the frame, in pixels
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 { self.width }
+
+    /// Height of the frame, in pixels
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 { self.height }
+
+    /// The pixel buffer, one byte per pixel, row-major from the top-left
+    #[wasm_bindgen(getter)]
+    pub fn pixels(&self) -> Vec<u8> { self.pixels.clone() }
+}
+
+impl Frame {
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Self {
+        Self { width, height, pixels }
+    }
+}
+
+/// A single digital input transition (key, button, etc.) to deliver to an emulator.
+#[wasm_bindgen]
+pub struct KeyEvent {
+    pub key:     u8,
+    pub pressed: bool,
+}
+
+#[wasm_bindgen]
+impl KeyEvent {
+    #[wasm_bindgen(constructor)]
+    pub fn new(key: u8, pressed: bool) -> Self {
+        Self { key, pressed }
+    }
+}
+
 /// Emulator trait to have standard calling interface across implementations.
 pub trait Emulator {
     /// Initialize a new emulator
@@ -23,8 +69,11 @@ pub trait Emulator {
     /// Draw loop
     fn e_draw(&mut self);
 
-    /// Set input maybe key or gamepad
-    fn e_set_input(&mut self);
+    /// Returns the current display contents as a host-renderable frame
+    fn present_frame(&self) -> Frame;
+
+    /// Feeds a single key transition into the emulator
+    fn e_set_input(&mut self, event: KeyEvent);
 
     /// Arbitrary reset function to reset the state of the emulator
     fn e_reset(&mut self);